@@ -1,11 +1,40 @@
-use rand::Rng;
+use rand::{Rng, RngCore, SeedableRng};
+use rand::rngs::StdRng;
 use clap::{App, Arg};
-use std::sync::mpsc;
-use crossbeam;
+use rayon::prelude::*;
 
 
+/// Anything that can be clustered by `cluster_points`: it needs a notion of distance to another
+/// instance of itself, and a way to collapse a group of instances down to their centroid.
+trait Clusterable: Sized {
+    /// A distance metric between `self` and `other`. `cluster_points` only ever compares
+    /// distances to each other, so implementations are free to return squared distance (or any
+    /// other monotonic stand-in for the true distance) to save the cost of a square root.
+    fn distance(&self, other: &Self) -> f64;
+
+    /// Collapses an iterator of items down to their centroid, or `None` if the iterator was empty.
+    fn centroid<'a>(items: impl Iterator<Item = &'a Self>) -> Option<Self> where Self: 'a;
+
+    /// This item's coordinates, in a fixed order, for writing out as a row of an output file.
+    fn coords(&self) -> Vec<f64>;
+
+    /// Given a list of clusters, we place ourself into the cluster with the closest centroid
+    fn find_best_cluster(&self, clusters: &[Cluster<'_, Self>]) -> usize {
+	let mut best_idx = 0;
+	let mut best_distance = f64::INFINITY;
+	for (i, cluster) in clusters.iter().enumerate() {
+	    let current_compare = self.distance(&cluster.centroid);
+	    if current_compare < best_distance {
+		best_distance = current_compare;
+		best_idx = i;
+	    }
+	}
+	best_idx
+    }
+}
+
 // A struct with an x, y coord
-#[derive(Debug, PartialEq, PartialOrd)]
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
 struct Point{
     x: f64,
     y: f64,
@@ -14,8 +43,7 @@ struct Point{
 
 impl Point {
     /// Construct a ranomd point within the given bounds
-    fn new_random_within_range(left: f64, right: f64, bottom: f64, top: f64) -> Self {
-	let mut rng = rand::thread_rng();
+    fn new_random_within_range(rng: &mut dyn RngCore, left: f64, right: f64, bottom: f64, top: f64) -> Self {
 	let x = rng.gen_range(left..=right);
 	let y = rng.gen_range(bottom..=top);
 	Point { x, y }
@@ -26,134 +54,369 @@ impl Point {
     fn squared_distance(p1: &Point, p2: &Point) -> f64 {
 	(p1.x - p2.x).powf(2.0) + (p1.y - p2.y).powf(2.0)
     }
-    
-    /// Given a list of clusters, we place ourself into the cluster with the closest centroid
-    fn find_best_cluster(&self, clusters: & Vec<Cluster>) -> usize{
-	let mut best_idx = 0;
-	let mut best_distance = f64::INFINITY;
-	for (i, cluster) in clusters.iter().enumerate() {
-	    let current_compare = Point::squared_distance(&self, &cluster.centroid);
-	    if current_compare < best_distance {
-		best_distance = current_compare;
-		best_idx = i;
+}
+
+impl Clusterable for Point {
+    fn distance(&self, other: &Self) -> f64 {
+	Point::squared_distance(self, other)
+    }
+
+    fn centroid<'a>(items: impl Iterator<Item = &'a Self>) -> Option<Self> where Self: 'a {
+	let mut sum_x = 0.;
+	let mut sum_y = 0.;
+	let mut count = 0usize;
+	for point in items {
+	    sum_x += point.x;
+	    sum_y += point.y;
+	    count += 1;
+	}
+	if count == 0 {
+	    return None;
+	}
+	Some(Point { x: sum_x / count as f64, y: sum_y / count as f64 })
+    }
+
+    fn coords(&self) -> Vec<f64> {
+	vec![self.x, self.y]
+    }
+}
+
+/// An N-dimensional point backed by a vector of coordinates, for clustering arbitrary feature
+/// vectors rather than just 2D points, e.g. rows loaded via `--input`.
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+struct VectorPoint {
+    coords: Vec<f64>,
+}
+
+impl VectorPoint {
+    fn new(coords: Vec<f64>) -> Self {
+	VectorPoint { coords }
+    }
+
+    /// Construct a random point whose i'th coordinate falls within `bounds[i]`'s (min, max) range.
+    fn new_random_within_bounds(rng: &mut dyn RngCore, bounds: &[(f64, f64)]) -> Self {
+	let coords = bounds.iter().map(|&(low, high)| rng.gen_range(low..=high)).collect();
+	VectorPoint::new(coords)
+    }
+
+    /// The per-dimension (min, max) bounds across all of `points`.
+    fn bounds(points: &[VectorPoint]) -> Vec<(f64, f64)> {
+	assert!(!points.is_empty(), "VectorPoint::bounds requires at least one point");
+	let dims = points[0].coords.len();
+	let mut bounds = vec![(f64::INFINITY, f64::NEG_INFINITY); dims];
+	for point in points {
+	    for (bound, &coord) in bounds.iter_mut().zip(point.coords.iter()) {
+		bound.0 = bound.0.min(coord);
+		bound.1 = bound.1.max(coord);
 	    }
 	}
-	best_idx
+	bounds
+    }
+}
+
+impl Clusterable for VectorPoint {
+    /// summed squared coordinate differences, i.e. the N-dimensional analogue of `Point::squared_distance`
+    fn distance(&self, other: &Self) -> f64 {
+	self.coords.iter().zip(other.coords.iter())
+	    .map(|(a, b)| (a - b).powf(2.0))
+	    .sum()
+    }
+
+    fn centroid<'a>(items: impl Iterator<Item = &'a Self>) -> Option<Self> where Self: 'a {
+	let mut sum: Vec<f64> = vec![];
+	let mut count = 0usize;
+	for item in items {
+	    if sum.is_empty() {
+		sum = vec![0.; item.coords.len()];
+	    }
+	    for (total, coord) in sum.iter_mut().zip(item.coords.iter()) {
+		*total += coord;
+	    }
+	    count += 1;
+	}
+	if count == 0 {
+	    return None;
+	}
+	for total in sum.iter_mut() {
+	    *total /= count as f64;
+	}
+	Some(VectorPoint::new(sum))
+    }
+
+    fn coords(&self) -> Vec<f64> {
+	self.coords.clone()
+    }
+}
+
+/// The strategy used to pick the initial centroids before the Lloyd loop begins.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum InitMethod {
+    /// Centroids are drawn uniformly at random from the bounding box.
+    Random,
+    /// Centroids are seeded from the input points using k-means++.
+    KMeansPlusPlus,
+}
+
+impl InitMethod {
+    /// Parses the `--init` flag value, falling back to kmeans++ on anything unrecognized.
+    fn from_str(s: &str) -> Self {
+	match s {
+	    "random" => InitMethod::Random,
+	    _ => InitMethod::KMeansPlusPlus,
+	}
     }
 }
 
 #[derive(Debug)]
-struct Cluster<'a> {
-    centroid: Point, // the centroid, i.e. the arithmetic mean of all consituent points
-    points: Vec<&'a Point>,
+struct Cluster<'a, T: Clusterable> {
+    centroid: T, // the centroid, i.e. the arithmetic mean of all consituent points
+    points: Vec<&'a T>,
 }
 
-impl <'a> Cluster <'a>
+impl <'a> Cluster <'a, Point>
 {
     /// the new_random function creates an empty cluster with a random centroid
-    fn new_random (left: f64, right: f64, bottom: f64, top: f64) -> Self {
+    fn new_random (rng: &mut dyn RngCore, left: f64, right: f64, bottom: f64, top: f64) -> Self {
+	Cluster {
+	    centroid: Point::new_random_within_range(rng, left, right, bottom, top),
+	    points: vec![],
+	}
+    }
+}
+
+impl <'a> Cluster <'a, VectorPoint>
+{
+    /// creates an empty cluster with a centroid drawn randomly from the given per-dimension bounds
+    fn new_random_within_bounds(rng: &mut dyn RngCore, bounds: &[(f64, f64)]) -> Self {
+	Cluster {
+	    centroid: VectorPoint::new_random_within_bounds(rng, bounds),
+	    points: vec![],
+	}
+    }
+}
+
+impl <'a, T: Clusterable + Clone> Cluster <'a, T>
+{
+    /// Creates an empty cluster with the given centroid (used by k-means++ seeding).
+    fn new_with_centroid(centroid: T) -> Self {
 	Cluster {
-	    centroid: Point::new_random_within_range(left, right, bottom, top),
+	    centroid,
 	    points: vec![],
 	}
     }
 
-    fn clear_points(&mut self) {
-    	self.points.clear();
+    /// Picks `num_clusters` centroids from `points` using k-means++: the first centroid is a
+    /// uniformly random point, and each following centroid is sampled from the remaining points
+    /// with probability proportional to its squared distance to the nearest centroid chosen so
+    /// far. This spreads the initial centroids out and avoids the poor convergence that plain
+    /// uniform-random seeding can produce.
+    fn kmeans_plusplus_seeds(rng: &mut dyn RngCore, points: &[T], num_clusters: usize) -> Vec<T> {
+	if num_clusters == 0 || points.is_empty() {
+	    return vec![];
+	}
+
+	let mut centroids: Vec<T> = Vec::with_capacity(num_clusters);
+
+	let first_idx = rng.gen_range(0..points.len());
+	centroids.push(points[first_idx].clone());
+
+	while centroids.len() < num_clusters {
+	    let sq_distances: Vec<f64> = points.iter().map(|point| {
+		centroids.iter()
+		    .map(|centroid| point.distance(centroid))
+		    .fold(f64::INFINITY, f64::min)
+	    }).collect();
+
+	    let total: f64 = sq_distances.iter().sum();
+	    let chosen_idx = if total <= 0.0 {
+		// every point coincides with an already-chosen centroid, so just pick one at random
+		rng.gen_range(0..points.len())
+	    } else {
+		let mut threshold = rng.gen_range(0.0..total);
+		let mut idx = points.len() - 1;
+		for (i, sq_distance) in sq_distances.iter().enumerate() {
+		    if threshold < *sq_distance {
+			idx = i;
+			break;
+		    }
+		    threshold -= *sq_distance;
+		}
+		idx
+	    };
+	    centroids.push(points[chosen_idx].clone());
+	}
+
+	centroids
     }
 
     /// This method iterates over all points within the cluster and updates self.centroid to be
     /// the arithmetic mean of all points.
     /// It returns how much the centroid was changed from the initial value before the function was called.
     fn set_centroid(&mut self) -> f64{
-	if self.points.len() == 0 {
-	    return 0.;
+	match T::centroid(self.points.iter().copied()) {
+	    Some(new_centroid) => {
+		let change = self.centroid.distance(&new_centroid);
+		self.centroid = new_centroid;
+		change
+	    },
+	    None => 0.,
 	}
-	let mut sum_x = 0.;
-	let mut sum_y = 0.;	
-	for point in & self.points {
-	    sum_x += point.x;
-	    sum_y += point.y;	    
-	}
-	let new_centroid = Point {x: sum_x / (self.points.len() as f64), y: sum_y / (self.points.len() as f64) };
-	let change = Point::squared_distance(&self.centroid, &new_centroid);
-	(*self).centroid = new_centroid;
-	change
     }
-}
-
 
-fn cluster_points<'a>(points: &'a Vec<Point>, num_clusters: usize, left: f64, right: f64, bottom: f64, top: f64, num_threads: usize)
-		      -> Vec<Cluster<'a>> {
-
-    // Initialize clusters with random centroids
-    let mut clusters = Vec::with_capacity(num_clusters);
-    for _ in 0..num_clusters {
-	let cluster = Cluster::new_random(left, right, bottom, top);
-	clusters.push(cluster);
+    /// The cluster's distortion: the sum of squared distances from its points to its centroid.
+    /// A cluster with low distortion and few points is "low-utility", a cluster with high
+    /// distortion is "high-utility" and a good candidate for splitting.
+    fn distortion(&self) -> f64 {
+	self.points.iter().map(|point| self.centroid.distance(point)).sum()
     }
+}
 
-    println!("Clusters to begin: {:?}", clusters);
-    
-    const EPSILON: f64 = 0.05; // this defines the threshold for when the clusters have converged
-    
+/// Finds the pair of points in `points` with the greatest pairwise distance, for use as the seed
+/// centroids when splitting a high-distortion cluster. O(n^2) in the size of `points`, which is
+/// fine since this only ever runs on a single cluster's worth of points during ELBG refinement.
+fn most_separated_pair<T: Clusterable + Clone>(points: &[&T]) -> (T, T) {
+    let mut best = (0, 1);
+    let mut best_distance = f64::NEG_INFINITY;
+    for i in 0..points.len() {
+	for j in (i + 1)..points.len() {
+	    let distance = points[i].distance(points[j]);
+	    if distance > best_distance {
+		best_distance = distance;
+		best = (i, j);
+	    }
+	}
+    }
+    (points[best.0].clone(), points[best.1].clone())
+}
 
-    // We construct as many chunks of the points vector as there are threads.
-    // For each interation in the loop, one thread will be responsible for all
-    // the points in a given chunk.
-    let point_chunks: Vec<& [Point] > = points.chunks(num_threads).collect();
-    let (sender, receiver) = mpsc::channel(); // when a point has found its best cluster, pass that info in the channel
+/// Runs an "enhanced LBG" refinement pass on top of the result of Lloyd iteration: repeatedly
+/// finds the lowest-utility cluster (fewest points, tie-broken by lowest distortion) and the
+/// highest-distortion cluster, tentatively removes the former and splits the latter in two by
+/// placing new centroids at its two most separated points, then reassigns the points that were
+/// in either cluster across the resulting centroids. The move is kept only if it strictly lowers
+/// total distortion; otherwise it's rolled back and refinement stops. This fixes clusters that
+/// Lloyd iteration left permanently empty, and tends to lower final distortion versus Lloyd alone.
+fn refine_with_elbg<'a, T: Clusterable + Clone>(clusters: Vec<Cluster<'a, T>>) -> Vec<Cluster<'a, T>> {
+    let mut clusters = clusters;
+
+    loop {
+	if clusters.len() < 2 {
+	    break;
+	}
 
+	let low_idx = (0..clusters.len()).min_by(|&a, &b| {
+	    clusters[a].points.len().cmp(&clusters[b].points.len())
+		.then_with(|| clusters[a].distortion().partial_cmp(&clusters[b].distortion()).unwrap())
+	}).unwrap();
 
-    let mut change = f64::INFINITY; // the overall change of all clusters' centroids    
-    // While the cluster centroids are still changing "enough", we keep re-assigning the points
-    while change > EPSILON {
-	for cluster in &mut clusters {
-	    // at the start of each loop, we clear all points from each cluster
-	    // so that they can be re-assigned to their closest cluster
-	   cluster.clear_points();
-	}
+	let high_idx = match (0..clusters.len())
+	    .filter(|&i| i != low_idx && clusters[i].points.len() >= 2)
+	    .max_by(|&a, &b| clusters[a].distortion().partial_cmp(&clusters[b].distortion()).unwrap())
 	{
-	    let clusters_ref = &clusters; // clusters_ref lets us move a reference to clusters into each thread
-	    for chunk in &point_chunks {
-		let sender_n = sender.clone(); // each thread needs its own clone of the sender
-		crossbeam::scope(|spawner| {
-		    // crosbeam scope ensures that all threads will be done before we move on,
-		    // this lets us safely borrow the points and clusters without them needing
-		    // a 'static lifetime
-		    spawner.spawn(move |_| {
-			for point in *chunk {	
-			    // find the best cluster for each point, and send the info into the channel
-			    let  best_idx = point.find_best_cluster(clusters_ref);
-			    sender_n.send((point, best_idx)).unwrap();
-			}
-		    });
-		}).unwrap();
+	    Some(idx) => idx,
+	    None => break, // no cluster has enough points left to split
+	};
+
+	let old_distortion = clusters[low_idx].distortion() + clusters[high_idx].distortion();
+
+	let (seed_a, seed_b) = most_separated_pair(&clusters[high_idx].points);
+	let mut split_a = Cluster::new_with_centroid(seed_a);
+	let mut split_b = Cluster::new_with_centroid(seed_b);
+
+	let affected_points: Vec<&'a T> = clusters[low_idx].points.iter()
+	    .chain(clusters[high_idx].points.iter())
+	    .copied()
+	    .collect();
+	for point in &affected_points {
+	    if point.distance(&split_a.centroid) <= point.distance(&split_b.centroid) {
+		split_a.points.push(point);
+	    } else {
+		split_b.points.push(point);
 	    }
 	}
+	split_a.set_centroid();
+	split_b.set_centroid();
 
-	// we iterate through each point assignment as grabbed from the receiver end of the channel
-	// and place each point into the corresponding cluster
-	let mut results_received = 0;
-	for (point, best_idx) in &receiver {
-	    results_received += 1;
-	    clusters[best_idx].points.push(point);
-	    if results_received >= points.len() {
-		// if we got a result for every point, then we are done
-		break;
-	    }
-	}
+	let new_distortion = split_a.distortion() + split_b.distortion();
 
-	change = 0.0;	
-	for cluster in &mut clusters {
-	    // Now that the points have been assigned, tell the clusters
-	    // to recalculate their centroids, and return how much of a change there was
-	    change += cluster.set_centroid();
+	if new_distortion < old_distortion {
+	    let mut next_clusters = Vec::with_capacity(clusters.len());
+	    for (i, cluster) in clusters.into_iter().enumerate() {
+		if i != low_idx && i != high_idx {
+		    next_clusters.push(cluster);
+		}
+	    }
+	    next_clusters.push(split_a);
+	    next_clusters.push(split_b);
+	    clusters = next_clusters;
+	} else {
+	    break; // the best available swap didn't help, so there's nothing left to gain
 	}
-	println!("change = {}", change);
     }
+
     clusters
-}    
+}
+
+
+fn cluster_points<'a, T: Clusterable + Clone + Send + Sync + std::fmt::Debug>(points: &'a [T], clusters: Vec<Cluster<'a, T>>, num_threads: usize)
+		      -> Vec<Cluster<'a, T>> {
+
+    println!("Clusters to begin: {:?}", clusters);
+
+    const EPSILON: f64 = 0.05; // this defines the threshold for when the clusters have converged
+
+    let num_clusters = clusters.len();
+
+    // Run the whole Lloyd loop inside a pool sized to num_threads, so the parallel assignment
+    // and reduction below actually use that many threads instead of however many rayon's global
+    // pool defaults to.
+    let pool = rayon::ThreadPoolBuilder::new()
+	.num_threads(num_threads)
+	.build()
+	.unwrap();
+
+    pool.install(|| {
+	let mut clusters = clusters;
+	let mut change = f64::INFINITY; // the overall change of all clusters' centroids
+	// While the cluster centroids are still changing "enough", we keep re-assigning the points
+	while change > EPSILON {
+	    // For every point, find its best cluster in parallel, folding the per-point
+	    // assignments into per-cluster point lists along the way, then reduce those partial
+	    // per-cluster lists together across threads. This replaces the old chunk-per-thread
+	    // + mpsc channel approach with a single parallel map/fold/reduce and no channel.
+	    let assigned_points: Vec<Vec<&'a T>> = points.par_iter()
+		.fold(
+		    || vec![Vec::new(); num_clusters],
+		    |mut acc: Vec<Vec<&'a T>>, point| {
+			let best_idx = point.find_best_cluster(&clusters);
+			acc[best_idx].push(point);
+			acc
+		    },
+		)
+		.reduce(
+		    || vec![Vec::new(); num_clusters],
+		    |mut a, b| {
+			for (a_points, b_points) in a.iter_mut().zip(b) {
+			    a_points.extend(b_points);
+			}
+			a
+		    },
+		);
+
+	    // Now that the points have been assigned, tell the clusters (in parallel) to recompute
+	    // their centroids, and sum up how much of a change there was overall.
+	    change = clusters.par_iter_mut()
+		.zip(assigned_points.into_par_iter())
+		.map(|(cluster, points)| {
+		    cluster.points = points;
+		    cluster.set_centroid()
+		})
+		.sum();
+	    println!("change = {}", change);
+	}
+	clusters
+    })
+}
 
 
 fn main() {
@@ -165,8 +428,8 @@ fn main() {
             .short("p")
             .long("num_points")
             //.value_name("NUM_POINTS")
-             .help("The number of random points to cluster")
-	     .required(true)	     
+             .help("The number of random points to cluster (ignored if --input is given)")
+	     .required_unless("input")
              .takes_value(true))
         .arg(Arg::with_name("num_clusters")
              .short("c")
@@ -183,38 +446,214 @@ fn main() {
 	     .required(false)
 	     .default_value("4")
             .takes_value(true))
+        .arg(Arg::with_name("init")
+             .short("i")
+             .long("init")
+             .help("The centroid initialization strategy to use: kmeans++ or random")
+	     .required(false)
+	     .possible_values(&["kmeans++", "random"])
+	     .default_value("kmeans++")
+            .takes_value(true))
+        .arg(Arg::with_name("elbg")
+             .long("elbg")
+             .help("Run an enhanced-LBG refinement pass after convergence to fix empty clusters and lower distortion")
+	     .required(false)
+	     .takes_value(false))
+        .arg(Arg::with_name("input")
+             .long("input")
+             .help("Path to a CSV or whitespace-separated file of points to cluster (one point per line, N coordinates each), instead of generating random 2D points")
+	     .required(false)
+	     .takes_value(true))
+        .arg(Arg::with_name("output")
+             .short("o")
+             .long("output")
+             .help("Path to write each input point's coordinates and assigned cluster index to")
+	     .required(false)
+	     .takes_value(true))
+        .arg(Arg::with_name("restarts")
+             .short("r")
+             .long("restarts")
+             .help("The number of times to run the whole clustering from scratch, keeping the run with the lowest inertia (WCSS)")
+	     .required(false)
+	     .default_value("1")
+	     .validator(|s| match s.parse::<usize>() {
+		 Ok(n) if n >= 1 => Ok(()),
+		 Ok(_) => Err(String::from("restarts must be at least 1")),
+		 Err(_) => Err(String::from("restarts must be a positive integer")),
+	     })
+	     .takes_value(true))
+        .arg(Arg::with_name("seed")
+             .long("seed")
+             .help("Seed the RNG used for centroid initialization, so runs are reproducible")
+	     .required(false)
+	     .validator(|s| s.parse::<u64>()
+		 .map(|_| ())
+		 .map_err(|_| String::from("seed must be a non-negative integer")))
+	     .takes_value(true))
         .get_matches();
 
 
-    // since both args are requied, we are free to unwrap
+    // since num_clusters is always required, we are free to unwrap
     let num_clusters: usize = matches.value_of("num_clusters").unwrap().parse().unwrap();
-    let num_points: usize = matches.value_of("num_points").unwrap().parse().unwrap();
-    let num_threads: usize = matches.value_of("num_threads").unwrap().parse().unwrap();     
+    let num_threads: usize = matches.value_of("num_threads").unwrap().parse().unwrap();
+    let init = InitMethod::from_str(matches.value_of("init").unwrap());
+    let elbg = matches.is_present("elbg");
+    let output_path = matches.value_of("output");
+    let restarts: usize = matches.value_of("restarts").unwrap().parse().unwrap();
+
+    let mut rng: Box<dyn RngCore> = match matches.value_of("seed") {
+	Some(seed) => Box::new(StdRng::seed_from_u64(seed.parse().unwrap())),
+	None => Box::new(rand::thread_rng()),
+    };
+
+    match matches.value_of("input") {
+	Some(input_path) => {
+	    let points = load_points_from_file(input_path);
+	    let bounds = VectorPoint::bounds(&points);
+
+	    let (clusters, inertia) = run_with_restarts(&points, num_threads, elbg, restarts, rng.as_mut(), |rng| {
+		match init {
+		    InitMethod::Random => {
+			(0..num_clusters).map(|_| Cluster::new_random_within_bounds(rng, &bounds)).collect()
+		    },
+		    InitMethod::KMeansPlusPlus => {
+			Cluster::kmeans_plusplus_seeds(rng, &points, num_clusters)
+			    .into_iter()
+			    .map(Cluster::new_with_centroid)
+			    .collect()
+		    },
+		}
+	    });
+	    report(&points, &clusters, inertia, output_path);
+	},
+	None => {
+	    let num_points: usize = matches.value_of("num_points").unwrap().parse().unwrap();
+
+	    // These constants define the boundary in the real plane where the points and clusters can exist
+	    const LEFT: f64 = -5.;
+	    const RIGHT: f64 = 5.;
+	    const BOTTOM: f64 = -5.;
+	    const TOP: f64 = 5.;
+
+	    // initialize our random points that will be clustered
+	    let mut points = Vec::with_capacity(num_points);
+	    for _ in 0..num_points {
+		let point = Point::new_random_within_range(rng.as_mut(), LEFT, RIGHT, BOTTOM, TOP);
+		points.push(point);
+	    }
 
+	    let (clusters, inertia) = run_with_restarts(&points, num_threads, elbg, restarts, rng.as_mut(), |rng| {
+		match init {
+		    InitMethod::Random => {
+			let mut clusters = Vec::with_capacity(num_clusters);
+			for _ in 0..num_clusters {
+			    clusters.push(Cluster::new_random(rng, LEFT, RIGHT, BOTTOM, TOP));
+			}
+			clusters
+		    },
+		    InitMethod::KMeansPlusPlus => {
+			Cluster::kmeans_plusplus_seeds(rng, &points, num_clusters)
+			    .into_iter()
+			    .map(Cluster::new_with_centroid)
+			    .collect()
+		    },
+		}
+	    });
+	    report(&points, &clusters, inertia, output_path);
+	},
+    }
+}
 
+/// Runs the whole Lloyd loop (plus optional ELBG refinement) `restarts` times, each with a fresh
+/// set of initial centroids built by `build_initial_clusters`, and keeps the run with the lowest
+/// inertia (total within-cluster sum of squares). Returns that run's clusters and its inertia.
+fn run_with_restarts<'a, T: Clusterable + Clone + Send + Sync + std::fmt::Debug>(
+    points: &'a [T],
+    num_threads: usize,
+    elbg: bool,
+    restarts: usize,
+    rng: &mut dyn RngCore,
+    mut build_initial_clusters: impl FnMut(&mut dyn RngCore) -> Vec<Cluster<'a, T>>,
+) -> (Vec<Cluster<'a, T>>, f64) {
+    let mut best: Option<(Vec<Cluster<'a, T>>, f64)> = None;
+
+    for _ in 0..restarts {
+	let initial_clusters = build_initial_clusters(rng);
+	let mut clusters = cluster_points(points, initial_clusters, num_threads);
+	if elbg {
+	    clusters = refine_with_elbg(clusters);
+	}
+	let inertia: f64 = clusters.iter().map(Cluster::distortion).sum();
+
+	let is_better = match &best {
+	    Some((_, best_inertia)) => inertia < *best_inertia,
+	    None => true,
+	};
+	if is_better {
+	    best = Some((clusters, inertia));
+	}
+    }
 
-    // These constants define the boundary in the real plane where the points and clusters can exist
-    const LEFT: f64 = -5.;
-    const RIGHT: f64 = 5.;
-    const BOTTOM: f64 = -5.;
-    const TOP: f64 = 5.;
+    best.expect("restarts must be at least 1")
+}
 
+/// Reads points from `path`, one point per line, with N coordinates per line separated by commas
+/// and/or whitespace.
+fn load_points_from_file(path: &str) -> Vec<VectorPoint> {
+    let contents = std::fs::read_to_string(path)
+	.unwrap_or_else(|e| panic!("failed to read input file {}: {}", path, e));
+
+    let points: Vec<VectorPoint> = contents.lines()
+	.map(str::trim)
+	.filter(|line| !line.is_empty())
+	.map(|line| {
+	    let coords: Vec<f64> = line.split(|c: char| c == ',' || c.is_whitespace())
+		.filter(|field| !field.is_empty())
+		.map(|field| field.parse().unwrap_or_else(|e| panic!("failed to parse {:?} as a number: {}", field, e)))
+		.collect();
+	    VectorPoint::new(coords)
+	})
+	.collect();
+
+    if points.is_empty() {
+	panic!("input file {} contained no points", path);
+    }
 
-    // initialize our random points that will be clustered
-    let mut points = Vec::with_capacity(num_points);
-    for _ in 0..num_points {
-	let point = Point::new_random_within_range(LEFT, RIGHT, BOTTOM, TOP);
-	points.push(point);
+    let dims = points[0].coords.len();
+    for (i, point) in points.iter().enumerate() {
+	if point.coords.len() != dims {
+	    panic!(
+		"input file {} has inconsistent dimensionality: point 1 has {} coordinate(s) but point {} has {}",
+		path, dims, i + 1, point.coords.len()
+	    );
+	}
     }
 
-    // call to function to cluster the points
-    let clusters = cluster_points(&points, num_clusters, LEFT, RIGHT, BOTTOM, TOP, num_threads);
+    points
+}
 
+/// Prints a summary of the final clusters, and if `output_path` is given, writes each point's
+/// coordinates plus the index of the cluster it was assigned to, one per line.
+fn report<T: Clusterable + std::fmt::Debug>(points: &[T], clusters: &[Cluster<T>], inertia: f64, output_path: Option<&str>) {
     for (i, cluster) in clusters.iter().enumerate() {
 	println!("Cluster {} has centroid at {:?} and {} points", i, cluster.centroid, cluster.points.len());
     }
-	
-
+    println!("Chosen run's inertia (WCSS): {}", inertia);
+
+    if let Some(path) = output_path {
+	// map each point (by address) to the index of the cluster whose point list holds it
+	let assignment: std::collections::HashMap<*const T, usize> = clusters.iter().enumerate()
+	    .flat_map(|(i, cluster)| cluster.points.iter().map(move |point| (*point as *const T, i)))
+	    .collect();
+	let mut contents = String::new();
+	for point in points {
+	    let cluster_idx = assignment[&(point as *const T)];
+	    let coord_strs: Vec<String> = point.coords().iter().map(|c| c.to_string()).collect();
+	    contents.push_str(&format!("{},{}\n", coord_strs.join(","), cluster_idx));
+	}
+	std::fs::write(path, contents)
+	    .unwrap_or_else(|e| panic!("failed to write output file {}: {}", path, e));
+    }
 }
 
 
@@ -226,10 +665,105 @@ mod tests {
     fn test_set_mean() {
 	let centroid = Point{ x: 3., y: 4. };
 	let points = vec![&Point{ x: 1., y: 1. }, &Point{ x: -1., y: -1. }] ;
-	let mut cluster = Cluster { centroid, points: points };
+	let mut cluster = Cluster { centroid, points };
 	let diff = cluster.set_centroid();
 	assert_eq!(cluster.centroid, Point{ x: 0., y: 0. });
 	// the new centroid will be (0, 0) which is 55 away in squared euclidean distance from (3, 4)
 	assert_eq!(diff, 25.0 );
     }
+
+    #[test]
+    fn test_kmeans_plusplus_seeds_empty_points_returns_empty() {
+	let mut rng = StdRng::seed_from_u64(0);
+	let points: Vec<Point> = vec![];
+	let centroids = Cluster::kmeans_plusplus_seeds(&mut rng, &points, 3);
+	assert!(centroids.is_empty());
+    }
+
+    #[test]
+    fn test_kmeans_plusplus_seeds_picks_distinct_centroids() {
+	let mut rng = StdRng::seed_from_u64(0);
+	let points = vec![
+	    Point{ x: 0., y: 0. },
+	    Point{ x: 0.1, y: 0. },
+	    Point{ x: 10., y: 10. },
+	    Point{ x: 10.1, y: 10. },
+	];
+	let centroids = Cluster::kmeans_plusplus_seeds(&mut rng, &points, 2);
+	assert_eq!(centroids.len(), 2);
+	assert_ne!(centroids[0], centroids[1]);
+    }
+
+    #[test]
+    #[should_panic(expected = "contained no points")]
+    fn test_load_points_from_file_rejects_empty_file() {
+	let path = std::env::temp_dir().join("k_means_test_empty_input.txt");
+	std::fs::write(&path, "   \n\n").unwrap();
+	load_points_from_file(path.to_str().unwrap());
+    }
+
+    #[test]
+    #[should_panic(expected = "inconsistent dimensionality")]
+    fn test_load_points_from_file_rejects_ragged_rows() {
+	let path = std::env::temp_dir().join("k_means_test_ragged_input.txt");
+	std::fs::write(&path, "1,2\n3,4,5\n").unwrap();
+	load_points_from_file(path.to_str().unwrap());
+    }
+
+    #[test]
+    fn test_run_with_restarts_is_reproducible_with_a_seeded_rng() {
+	let points = vec![
+	    Point{ x: 0., y: 0. },
+	    Point{ x: 0.1, y: 0. },
+	    Point{ x: 10., y: 10. },
+	    Point{ x: 10.1, y: 10. },
+	];
+
+	let run = || {
+	    let mut rng = StdRng::seed_from_u64(42);
+	    let (_, inertia) = run_with_restarts(&points, 1, false, 3, &mut rng, |rng| {
+		Cluster::kmeans_plusplus_seeds(rng, &points, 2)
+		    .into_iter()
+		    .map(Cluster::new_with_centroid)
+		    .collect()
+	    });
+	    inertia
+	};
+
+	assert_eq!(run(), run());
+    }
+
+    #[test]
+    fn test_vector_point_centroid() {
+	let a = VectorPoint::new(vec![1., 1., 1.]);
+	let b = VectorPoint::new(vec![-1., -1., -1.]);
+	let centroid = VectorPoint::centroid(vec![&a, &b].into_iter()).unwrap();
+	assert_eq!(centroid, VectorPoint::new(vec![0., 0., 0.]));
+    }
+
+    #[test]
+    fn test_refine_with_elbg_fixes_empty_cluster_and_lowers_distortion() {
+	let tight_a = Point{ x: 0., y: 0. };
+	let tight_b = Point{ x: 0., y: 2. };
+	let tight_cluster = Cluster { centroid: Point{ x: 0., y: 1. }, points: vec![&tight_a, &tight_b] };
+
+	let empty_cluster = Cluster { centroid: Point{ x: 5., y: 5. }, points: vec![] };
+
+	let spread_a = Point{ x: 10., y: 10. };
+	let spread_b = Point{ x: 10., y: -10. };
+	let spread_c = Point{ x: -10., y: 10. };
+	let spread_cluster = Cluster {
+	    centroid: Point{ x: 0., y: 0. },
+	    points: vec![&spread_a, &spread_b, &spread_c],
+	};
+
+	let clusters = vec![tight_cluster, empty_cluster, spread_cluster];
+	let old_distortion: f64 = clusters.iter().map(Cluster::distortion).sum();
+
+	let refined = refine_with_elbg(clusters);
+
+	let new_distortion: f64 = refined.iter().map(Cluster::distortion).sum();
+	assert!(new_distortion < old_distortion);
+	assert!(refined.iter().all(|cluster| !cluster.points.is_empty()));
+    }
 }